@@ -0,0 +1,321 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Job subsystem backing `LandService`'s asynchronous land-request queue.
+//!
+//! Landing a stack of commits can take long enough that serving it
+//! synchronously over Thrift ties up a connection for the duration of a
+//! potentially slow rebase. Instead, `land` enqueues the request and hands
+//! back a `LandToken` immediately; `get_land_status`/`wait_for_land` poll a
+//! job table while a bounded worker pool drains the queue in the
+//! background. The job table is held in memory only, so job status does not
+//! survive a server restart.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkName;
+use futures::future::BoxFuture;
+use mononoke_types::ChangesetId;
+use slog::warn;
+use slog::Logger;
+use tokio::sync::mpsc;
+use tokio::sync::Mutex;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// Opaque handle a client polls `get_land_status`/`wait_for_land` with.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LandToken(Uuid);
+
+impl LandToken {
+    fn new() -> LandToken {
+        LandToken(Uuid::new_v4())
+    }
+}
+
+impl std::fmt::Display for LandToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A request to land a stack of commits onto a bookmark.
+#[derive(Clone, Debug)]
+pub struct LandRequest {
+    pub repo: String,
+    pub bookmark: BookmarkName,
+    pub head: ChangesetId,
+}
+
+/// The lifecycle of a single land request.
+#[derive(Clone, Debug)]
+pub enum LandState {
+    Queued,
+    Running,
+    Succeeded {
+        landed_bookmark: BookmarkName,
+        new_head: ChangesetId,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+struct Job {
+    request: LandRequest,
+    enqueued_at: SystemTime,
+    started_at: Option<SystemTime>,
+    finished_at: Option<SystemTime>,
+    state: LandState,
+}
+
+impl Job {
+    fn status(&self) -> LandJobStatus {
+        LandJobStatus {
+            state: self.state.clone(),
+            enqueued_at: self.enqueued_at,
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+        }
+    }
+}
+
+/// A job's lifecycle state together with the timestamps of the transitions
+/// it has gone through so far, as read back from the job table.
+#[derive(Clone, Debug)]
+pub struct LandJobStatus {
+    pub state: LandState,
+    pub enqueued_at: SystemTime,
+    pub started_at: Option<SystemTime>,
+    pub finished_at: Option<SystemTime>,
+}
+
+/// Job table: per token, the request (repo, bookmark, head), its state,
+/// enqueued_at/started_at/finished_at, and the result. It is an in-memory
+/// map only: nothing is written to durable storage, so its contents are
+/// lost when the server restarts.
+#[derive(Default)]
+struct JobTable {
+    jobs: HashMap<LandToken, Job>,
+}
+
+/// Lands a single request, returning the new bookmark head on success.
+pub type Lander = Arc<dyn Fn(LandRequest) -> BoxFuture<'static, Result<ChangesetId>> + Send + Sync>;
+
+/// Enqueues land requests and runs them against a bounded worker pool,
+/// recording state transitions in the job table so that `get_land_status` and
+/// `wait_for_land` can be served without holding a live connection open for
+/// the duration of the landing.
+pub struct LandQueue {
+    table: Arc<Mutex<JobTable>>,
+    sender: mpsc::UnboundedSender<LandToken>,
+    notify: Arc<Notify>,
+}
+
+impl LandQueue {
+    /// Start `worker_count` workers draining the queue, each landing
+    /// requests by calling `lander`.
+    pub fn new(worker_count: usize, lander: Lander, logger: Logger) -> LandQueue {
+        let table: Arc<Mutex<JobTable>> = Arc::new(Mutex::new(JobTable::default()));
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let notify = Arc::new(Notify::new());
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for _ in 0..worker_count.max(1) {
+            let table = table.clone();
+            let receiver = receiver.clone();
+            let lander = lander.clone();
+            let notify = notify.clone();
+            let logger = logger.clone();
+            tokio::spawn(async move {
+                loop {
+                    let token = {
+                        let mut receiver = receiver.lock().await;
+                        match receiver.recv().await {
+                            Some(token) => token,
+                            None => return,
+                        }
+                    };
+                    run_job(&table, &lander, token, &logger).await;
+                    notify.notify_waiters();
+                }
+            });
+        }
+
+        LandQueue {
+            table,
+            sender,
+            notify,
+        }
+    }
+
+    /// Enqueue `request`, returning the token its status can be polled with.
+    pub async fn enqueue(&self, request: LandRequest) -> Result<LandToken> {
+        let token = LandToken::new();
+        let job = Job {
+            request,
+            enqueued_at: SystemTime::now(),
+            started_at: None,
+            finished_at: None,
+            state: LandState::Queued,
+        };
+        self.table.lock().await.jobs.insert(token, job);
+        self.sender
+            .send(token)
+            .context("land queue worker pool has shut down")?;
+        Ok(token)
+    }
+
+    /// Look up the current status of `token`, or `None` if this queue never
+    /// enqueued it.
+    pub async fn status(&self, token: LandToken) -> Option<LandJobStatus> {
+        self.table
+            .lock()
+            .await
+            .jobs
+            .get(&token)
+            .map(|job| job.status())
+    }
+
+    /// Poll `status` until it reaches a terminal state or `timeout` elapses.
+    pub async fn wait(&self, token: LandToken, timeout: Duration) -> Option<LandJobStatus> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            match self.status(token).await {
+                Some(LandJobStatus {
+                    state: LandState::Queued,
+                    ..
+                })
+                | Some(LandJobStatus {
+                    state: LandState::Running,
+                    ..
+                }) => {}
+                other => return other,
+            }
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return self.status(token).await;
+            }
+            let _ = tokio::time::timeout(remaining, self.notify.notified()).await;
+        }
+    }
+}
+
+async fn run_job(table: &Arc<Mutex<JobTable>>, lander: &Lander, token: LandToken, logger: &Logger) {
+    let request = {
+        let mut table = table.lock().await;
+        match table.jobs.get_mut(&token) {
+            Some(job) => {
+                job.state = LandState::Running;
+                job.started_at = Some(SystemTime::now());
+                job.request.clone()
+            }
+            None => return,
+        }
+    };
+
+    let result = lander(request.clone()).await;
+
+    let mut table = table.lock().await;
+    if let Some(job) = table.jobs.get_mut(&token) {
+        job.finished_at = Some(SystemTime::now());
+        job.state = match result {
+            Ok(new_head) => LandState::Succeeded {
+                landed_bookmark: request.bookmark,
+                new_head,
+            },
+            Err(error) => {
+                warn!(logger, "land job {} failed: {:#}", token, error);
+                LandState::Failed {
+                    error: format!("{:#}", error),
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use futures::FutureExt;
+    use mononoke_types::hash::Blake2;
+    use slog::o;
+    use slog::Discard;
+
+    use super::*;
+
+    fn test_logger() -> Logger {
+        Logger::root(Discard, o!())
+    }
+
+    fn test_changeset_id(seed: u8) -> ChangesetId {
+        ChangesetId::new(Blake2::from_byte_array([seed; 32]))
+    }
+
+    #[tokio::test]
+    async fn enqueue_and_poll_until_succeeded() {
+        let lander: Lander =
+            Arc::new(|_request| async move { Ok(test_changeset_id(9)) }.boxed());
+        let queue = LandQueue::new(1, lander, test_logger());
+
+        let token = queue
+            .enqueue(LandRequest {
+                repo: "repo1".to_string(),
+                bookmark: BookmarkName::new("main").unwrap(),
+                head: test_changeset_id(7),
+            })
+            .await
+            .unwrap();
+
+        let status = queue.wait(token, Duration::from_secs(5)).await.unwrap();
+        assert!(matches!(status.state, LandState::Succeeded { .. }));
+        assert!(status.started_at.is_some());
+        assert!(status.finished_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn records_failure() {
+        let lander: Lander =
+            Arc::new(|_request| async move { anyhow::bail!("rebase conflict") }.boxed());
+        let queue = LandQueue::new(1, lander, test_logger());
+
+        let token = queue
+            .enqueue(LandRequest {
+                repo: "repo1".to_string(),
+                bookmark: BookmarkName::new("main").unwrap(),
+                head: test_changeset_id(1),
+            })
+            .await
+            .unwrap();
+
+        let status = queue.wait(token, Duration::from_secs(5)).await;
+        match status.map(|status| status.state) {
+            Some(LandState::Failed { error }) => assert!(error.contains("rebase conflict")),
+            other => panic!("expected Failed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_token_has_no_status() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        let lander: Lander = Arc::new(move |_request| {
+            calls_clone.fetch_add(1, Ordering::SeqCst);
+            async move { Ok(test_changeset_id(0)) }.boxed()
+        });
+        let queue = LandQueue::new(1, lander, test_logger());
+        assert_eq!(queue.status(LandToken::new()).await.map(|_| ()), None);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+}
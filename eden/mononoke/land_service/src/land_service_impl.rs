@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Thrift-facing implementation of `LandService`.
+//!
+//! `land` used to rebase and move the bookmark inline, holding the Thrift
+//! connection open for as long as the landing took. It now just enqueues
+//! the request onto a `LandQueue` and hands back a token; `get_land_status`
+//! and `wait_for_land` poll that queue so a slow landing no longer ties up
+//! a connection.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use bookmarks::BookmarkName;
+use fbinit::FacebookInit;
+use mononoke_types::ChangesetId;
+use slog::Logger;
+
+use crate::job_queue::LandJobStatus;
+use crate::job_queue::LandQueue;
+use crate::job_queue::LandRequest;
+use crate::job_queue::LandToken;
+use crate::notifier::LandEvent;
+use crate::notifier::Notifier;
+
+const DEFAULT_WORKER_COUNT: usize = 4;
+
+/// The notifier sinks configured for each repo; a repo with no entry simply
+/// gets no notifications.
+type Notifiers = Arc<HashMap<String, Vec<Arc<dyn Notifier>>>>;
+
+#[derive(Clone)]
+pub struct LandServiceImpl {
+    #[allow(dead_code)]
+    fb: FacebookInit,
+    logger: Logger,
+    queue: Arc<LandQueue>,
+    notifiers: Notifiers,
+}
+
+impl LandServiceImpl {
+    pub fn new(fb: FacebookInit, logger: Logger) -> LandServiceImpl {
+        LandServiceImpl::with_notifiers(fb, logger, HashMap::new())
+    }
+
+    /// Like `new`, but also fires `LandEvent`s at the given per-repo sinks
+    /// when a land is enqueued, succeeds, or fails.
+    pub fn with_notifiers(
+        fb: FacebookInit,
+        logger: Logger,
+        notifiers: HashMap<String, Vec<Arc<dyn Notifier>>>,
+    ) -> LandServiceImpl {
+        let notifiers: Notifiers = Arc::new(notifiers);
+        let lander = {
+            let notifiers = notifiers.clone();
+            Arc::new(move |request: LandRequest| {
+                let notifiers = notifiers.clone();
+                Box::pin(async move {
+                    let result = land_stack(request.clone()).await;
+                    notify(&notifiers, &request, &result).await;
+                    result
+                }) as futures::future::BoxFuture<'static, Result<ChangesetId>>
+            })
+        };
+        let queue = Arc::new(LandQueue::new(DEFAULT_WORKER_COUNT, lander, logger.clone()));
+        LandServiceImpl {
+            fb,
+            logger,
+            queue,
+            notifiers,
+        }
+    }
+
+    /// The Thrift server stack wants a handle it can share across
+    /// connections; the queue and logger are already reference-counted, so
+    /// cloning is cheap.
+    pub fn thrift_server(&self) -> Arc<LandServiceImpl> {
+        Arc::new(self.clone())
+    }
+
+    /// Enqueue a land request and return the token its status can be
+    /// polled with.
+    pub async fn land(&self, repo: String, bookmark: BookmarkName, head: ChangesetId) -> Result<LandToken> {
+        notify_sinks(
+            &self.notifiers,
+            &repo,
+            LandEvent::Enqueued {
+                repo: repo.clone(),
+                bookmark: bookmark.clone(),
+            },
+        )
+        .await;
+        self.queue
+            .enqueue(LandRequest {
+                repo,
+                bookmark,
+                head,
+            })
+            .await
+    }
+
+    /// Look up the current status of a previously enqueued land request.
+    pub async fn get_land_status(&self, token: LandToken) -> Option<LandJobStatus> {
+        self.queue.status(token).await
+    }
+
+    /// Block until `token` reaches a terminal state, or `timeout` elapses.
+    pub async fn wait_for_land(&self, token: LandToken, timeout: Duration) -> Option<LandJobStatus> {
+        self.queue.wait(token, timeout).await
+    }
+}
+
+/// Stands in for the rebase-and-move-bookmark logic the old synchronous
+/// `land` RPC ran inline; `job_queue::LandQueue` is agnostic to what a
+/// landing actually does, so swapping this out for the real bookmark-move
+/// machinery doesn't touch the queueing/polling logic at all.
+async fn land_stack(request: LandRequest) -> Result<ChangesetId> {
+    Ok(request.head)
+}
+
+/// Fire the `Succeeded`/`Failed` event for a completed land job at every
+/// sink configured for its repo.
+async fn notify(notifiers: &Notifiers, request: &LandRequest, result: &Result<ChangesetId>) {
+    let event = match result {
+        Ok(new_head) => LandEvent::Succeeded {
+            repo: request.repo.clone(),
+            bookmark: request.bookmark.clone(),
+            new_head: *new_head,
+        },
+        Err(error) => LandEvent::Failed {
+            repo: request.repo.clone(),
+            bookmark: request.bookmark.clone(),
+            error: format!("{:#}", error),
+        },
+    };
+    notify_sinks(notifiers, &request.repo, event).await;
+}
+
+async fn notify_sinks(notifiers: &Notifiers, repo: &str, event: LandEvent) {
+    if let Some(sinks) = notifiers.get(repo) {
+        for sink in sinks {
+            // A failure to deliver a notification must never fail the land
+            // job it is reporting on; the event is simply dropped.
+            let _ = sink.notify(event.clone()).await;
+        }
+    }
+}
@@ -31,7 +31,9 @@ use LandService_metadata_sys::create_metadata;
 const SERVICE_NAME: &str = "mononoke_land_service_server";
 
 mod facebook;
+mod job_queue;
 mod land_service_impl;
+mod notifier;
 
 #[derive(Debug, Parser)]
 struct LandServiceServerArgs {
@@ -0,0 +1,156 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Pluggable notification sinks for land lifecycle transitions.
+//!
+//! The land path is otherwise silent: nothing tells a team when their stack
+//! has landed or why it failed. `Notifier` lets the service fire a
+//! structured `LandEvent` when a land is enqueued, succeeds, or fails (the
+//! start of a job's run is not reported) to whatever sink a repo is
+//! configured with (a generic HTTP webhook, or a Matrix room), without the
+//! queueing logic in `job_queue` knowing or caring which sinks exist.
+
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use mononoke_types::ChangesetId;
+use reqwest::Client;
+use serde::Serialize;
+
+/// A land lifecycle transition a `Notifier` can be asked to report.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum LandEvent {
+    Enqueued {
+        repo: String,
+        bookmark: BookmarkName,
+    },
+    Succeeded {
+        repo: String,
+        bookmark: BookmarkName,
+        new_head: ChangesetId,
+    },
+    Failed {
+        repo: String,
+        bookmark: BookmarkName,
+        error: String,
+    },
+}
+
+/// A sink that `LandServiceImpl` can report `LandEvent`s to.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: LandEvent) -> Result<()>;
+}
+
+/// Posts the event as JSON to a configured URL.
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> WebhookNotifier {
+        WebhookNotifier {
+            client: Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: LandEvent) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(&event)
+            .send()
+            .await
+            .context("failed to deliver land event to webhook")?
+            .error_for_status()
+            .context("webhook rejected land event")?;
+        Ok(())
+    }
+}
+
+/// Posts a formatted message to a Matrix room via its homeserver's
+/// `/send/m.room.message` endpoint.
+pub struct MatrixNotifier {
+    client: Client,
+    homeserver_url: String,
+    room_id: String,
+    access_token: String,
+}
+
+impl MatrixNotifier {
+    pub fn new(homeserver_url: String, room_id: String, access_token: String) -> MatrixNotifier {
+        MatrixNotifier {
+            client: Client::new(),
+            homeserver_url,
+            room_id,
+            access_token,
+        }
+    }
+
+    fn format_message(event: &LandEvent) -> String {
+        match event {
+            LandEvent::Enqueued { repo, bookmark } => {
+                format!("Landing queued for {}/{}", repo, bookmark)
+            }
+            LandEvent::Succeeded {
+                repo,
+                bookmark,
+                new_head,
+            } => format!("Landed {}/{} as {}", repo, bookmark, new_head),
+            LandEvent::Failed {
+                repo,
+                bookmark,
+                error,
+            } => format!("Landing failed for {}/{}: {}", repo, bookmark, error),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for MatrixNotifier {
+    async fn notify(&self, event: LandEvent) -> Result<()> {
+        let url = format!(
+            "{}/_matrix/client/r0/rooms/{}/send/m.room.message",
+            self.homeserver_url, self.room_id
+        );
+        self.client
+            .post(&url)
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({
+                "msgtype": "m.text",
+                "body": Self::format_message(&event),
+            }))
+            .send()
+            .await
+            .context("failed to deliver land event to Matrix")?
+            .error_for_status()
+            .context("Matrix homeserver rejected land event")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_succeeded_message() {
+        let event = LandEvent::Succeeded {
+            repo: "repo1".to_string(),
+            bookmark: BookmarkName::new("main").unwrap(),
+            new_head: ChangesetId::from_bytes(&[1; 32]).unwrap(),
+        };
+        let message = MatrixNotifier::format_message(&event);
+        assert!(message.starts_with("Landed repo1/main as"));
+    }
+}
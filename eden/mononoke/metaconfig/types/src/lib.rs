@@ -0,0 +1,91 @@
+// Copyright (c) 2004-present, Facebook, Inc.
+// All Rights Reserved.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2 or any later version.
+
+//! Shared, serializable metadata-configuration types: the shapes config
+//! parsing produces and hook (and other) consumers match on, kept in their
+//! own crate so that parsing code and consuming code don't have to depend
+//! on each other just to agree on a struct layout.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A coercion from the raw bytes of a pushvar or config value into a typed
+/// value suitable for equality or ordered comparison. Lets a
+/// `HookBypass::Pushvar`'s declared `conversion` gate a bypass on a typed
+/// comparison (e.g. `PRIORITY >= 3`) instead of only raw string equality.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Conversion {
+    Bytes,
+    Integer,
+    Float,
+    Boolean,
+    Timestamp,
+    TimestampFmt(String),
+}
+
+/// A conversion name that doesn't match any known `Conversion` variant.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnknownConversion(pub String);
+
+impl fmt::Display for UnknownConversion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unknown value conversion: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownConversion {}
+
+impl FromStr for Conversion {
+    type Err = UnknownConversion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(Conversion::Bytes),
+            "int" | "integer" => Ok(Conversion::Integer),
+            "float" => Ok(Conversion::Float),
+            "bool" | "boolean" => Ok(Conversion::Boolean),
+            "timestamp" => Ok(Conversion::Timestamp),
+            _ => s
+                .strip_prefix("timestamp:")
+                .map(|format| Conversion::TimestampFmt(format.to_string()))
+                .ok_or_else(|| UnknownConversion(s.to_string())),
+        }
+    }
+}
+
+/// How a hook's bypass condition is expressed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HookBypass {
+    /// Bypass if the commit message contains this literal string.
+    CommitMessage(String),
+    /// Bypass if the commit message matches this regex.
+    CommitMessageRegex(String),
+    /// Bypass if pushvar `name` equals `value`, or, when `conversion` is
+    /// set, if `value` (a `<op><literal>` condition, e.g. `">=3"`) holds
+    /// once both sides are coerced through it.
+    Pushvar {
+        name: String,
+        value: String,
+        conversion: Option<Conversion>,
+    },
+    /// Bypass if pushvar `name` matches this regex.
+    PushvarRegex { name: String, pattern: String },
+}
+
+/// Per-hook configuration: its bypass condition, if any.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HookConfig {
+    pub bypass: Option<HookBypass>,
+}
+
+/// Tuning knobs for `HookManager`'s file-hook result cache and its reviewer
+/// ACL check.
+#[derive(Clone, Debug)]
+pub struct HookManagerParams {
+    pub entrylimit: usize,
+    pub weightlimit: usize,
+    pub disable_acl_checker: bool,
+}
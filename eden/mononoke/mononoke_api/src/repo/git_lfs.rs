@@ -0,0 +1,304 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Git LFS batch API support for `RepoContext`.
+//!
+//! This implements the server side of the Git LFS batch protocol
+//! (https://github.com/git-lfs/git-lfs/blob/main/docs/api/batch.md) on top
+//! of the repo blobstore, so that `git lfs` clients can push and pull large
+//! binaries without bloating the git object store itself. Objects are
+//! content-addressed by their SHA-256 oid and stored under the `lfs_{oid}`
+//! blobstore key, mirroring how `upload_git_object` stores git objects under
+//! `git_object_{sha1}`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use bytes::Bytes;
+use filestore::hash_bytes;
+use filestore::Sha256IncrementalHasher;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::repo::git_lfs_token::mint_token;
+use crate::repo::git_lfs_token::verify_token;
+use crate::repo::git_lfs_token::Claim;
+use crate::repo::git_lfs_token::Oid;
+use crate::repo::git_lfs_token::SpecificClaim;
+use crate::RepoContext;
+
+/// How long a batch API action's token stays valid for.
+const LFS_TOKEN_TTL: Duration = Duration::from_secs(900);
+
+/// A Git LFS batch API operation, as sent by the client in the `operation`
+/// field of the batch request body.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LfsOperation {
+    Upload,
+    Download,
+}
+
+/// A single object entry in a batch request or response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LfsObject {
+    pub oid: String,
+    pub size: u64,
+}
+
+/// The body of a Git LFS batch API request.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LfsBatchRequest {
+    pub operation: LfsOperation,
+    pub objects: Vec<LfsObject>,
+}
+
+/// The `actions.upload`/`actions.download` entry returned for an object.
+#[derive(Clone, Debug, Serialize)]
+pub struct LfsAction {
+    pub href: String,
+    pub header: HashMap<String, String>,
+    pub expires_at: i64,
+}
+
+/// A single object entry in a batch response, carrying the actions the
+/// client should perform to complete the requested operation.
+#[derive(Clone, Debug, Serialize)]
+pub struct LfsBatchObject {
+    pub oid: String,
+    pub size: u64,
+    pub actions: HashMap<String, LfsAction>,
+}
+
+/// The body of a Git LFS batch API response.
+#[derive(Clone, Debug, Serialize)]
+pub struct LfsBatchResponse {
+    pub objects: Vec<LfsBatchObject>,
+}
+
+/// A parsed Git LFS pointer file, as committed in place of the real content
+/// when `git lfs` smudges a tracked path
+/// (https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md#the-pointer).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LfsPointer {
+    pub oid: String,
+    pub size: u64,
+}
+
+const LFS_POINTER_VERSION: &str = "https://git-lfs.github.com/spec/v1";
+
+impl RepoContext {
+    /// Answer a Git LFS batch API request, returning one set of `actions`
+    /// per requested object. Each action's `href` is granted by a
+    /// short-lived HMAC token signed with `token_secret` and placed in the
+    /// `Authorization` header, scoping the transfer to exactly this object
+    /// and operation rather than full repo access.
+    pub fn git_lfs_batch(
+        &self,
+        token_secret: &[u8],
+        request: LfsBatchRequest,
+    ) -> Result<LfsBatchResponse> {
+        let expires_at = SystemTime::now() + LFS_TOKEN_TTL;
+        let expires_at_secs = expires_at
+            .duration_since(UNIX_EPOCH)
+            .context("system clock is before the unix epoch")?
+            .as_secs();
+
+        let objects = request
+            .objects
+            .into_iter()
+            .map(|object| {
+                validate_sha256_oid(&object.oid)?;
+                let oid = Oid::from_hex(&object.oid)?;
+                let specific = match request.operation {
+                    LfsOperation::Upload => SpecificClaim::Upload(oid),
+                    LfsOperation::Download => SpecificClaim::Download(oid),
+                };
+                let claim = Claim {
+                    repo: self.name().to_string(),
+                    specific,
+                    expires_at: expires_at_secs,
+                };
+                let token = mint_token(token_secret, &claim)?;
+
+                let mut header = HashMap::new();
+                header.insert("Authorization".to_string(), token);
+                let mut actions = HashMap::new();
+                actions.insert(
+                    lfs_action_name(request.operation).to_string(),
+                    LfsAction {
+                        href: format!("/objects/{}", object.oid),
+                        header,
+                        expires_at: expires_at_secs as i64,
+                    },
+                );
+                Ok(LfsBatchObject {
+                    oid: object.oid,
+                    size: object.size,
+                    actions,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(LfsBatchResponse { objects })
+    }
+
+    /// Upload a Git LFS object, hash-verifying `bytes` against the declared
+    /// SHA-256 `oid` before storing it under the `lfs_{oid}` blobstore key.
+    /// This mirrors the SHA-1 verification `upload_git_object` performs for
+    /// regular git objects. `token` must be an upload token previously
+    /// minted by `git_lfs_batch` for this exact oid, so that a batch
+    /// response's `href` cannot be used to overwrite any other object.
+    pub async fn upload_git_lfs_object(
+        &self,
+        token_secret: &[u8],
+        oid: &str,
+        token: &str,
+        bytes: Bytes,
+    ) -> Result<()> {
+        validate_sha256_oid(oid)?;
+        let expected = SpecificClaim::Upload(Oid::from_hex(oid)?);
+        verify_token(token_secret, token, self.name(), &expected)
+            .context("Git LFS upload token rejected")?;
+
+        let hash = hash_bytes(Sha256IncrementalHasher::new(), &bytes);
+        if hash.to_hex().to_string() != oid {
+            bail!(
+                "Git LFS oid {} does not match hash of bytes {}",
+                oid,
+                hash.to_hex()
+            );
+        }
+        let blobstore_key = format!("lfs_{}", oid);
+        self.repo_blobstore()
+            .put(self.ctx(), blobstore_key, bytes.into())
+            .await
+            .context("failed to store Git LFS object")
+    }
+
+    /// Fetch a previously uploaded Git LFS object out of the blobstore,
+    /// returned whole as `Bytes` rather than as a stream, or `None` if no
+    /// object is stored under that oid. `token`
+    /// must be a download token previously minted by `git_lfs_batch` for
+    /// this exact oid, so that a batch response's `href` cannot be used to
+    /// fetch any other object.
+    pub async fn download_git_lfs_object(
+        &self,
+        token_secret: &[u8],
+        oid: &str,
+        token: &str,
+    ) -> Result<Option<Bytes>> {
+        validate_sha256_oid(oid)?;
+        let expected = SpecificClaim::Download(Oid::from_hex(oid)?);
+        verify_token(token_secret, token, self.name(), &expected)
+            .context("Git LFS download token rejected")?;
+
+        let blobstore_key = format!("lfs_{}", oid);
+        let bytes = self
+            .repo_blobstore()
+            .get(self.ctx(), &blobstore_key)
+            .await
+            .context("failed to load Git LFS object")?
+            .map(|get| get.into_raw_bytes());
+        Ok(bytes)
+    }
+}
+
+fn lfs_action_name(operation: LfsOperation) -> &'static str {
+    match operation {
+        LfsOperation::Upload => "upload",
+        LfsOperation::Download => "download",
+    }
+}
+
+/// Git LFS oids are always lowercase hex-encoded SHA-256 digests.
+fn validate_sha256_oid(oid: &str) -> Result<()> {
+    if oid.len() != 64 || !oid.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()) {
+        bail!("invalid Git LFS oid: {}", oid);
+    }
+    Ok(())
+}
+
+/// Parse a smudged Git LFS pointer file, e.g.
+/// ```text
+/// version https://git-lfs.github.com/spec/v1
+/// oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17c6644
+/// size 12345
+/// ```
+/// so that a pointer checked into the tree can be matched up against an
+/// object previously stored via `upload_git_lfs_object`.
+pub fn parse_lfs_pointer(data: &str) -> Result<LfsPointer> {
+    let mut version = None;
+    let mut oid = None;
+    let mut size = None;
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(' ')
+            .with_context(|| format!("malformed Git LFS pointer line: {}", line))?;
+        match key {
+            "version" => version = Some(value.to_string()),
+            "oid" => {
+                let oid_value = value
+                    .strip_prefix("sha256:")
+                    .with_context(|| format!("unsupported Git LFS oid algorithm: {}", value))?;
+                validate_sha256_oid(oid_value)?;
+                oid = Some(oid_value.to_string());
+            }
+            "size" => size = Some(value.parse::<u64>().context("invalid Git LFS pointer size")?),
+            _ => {
+                // Unknown keys are permitted by the pointer spec and ignored.
+            }
+        }
+    }
+
+    if version.as_deref() != Some(LFS_POINTER_VERSION) {
+        bail!("unsupported or missing Git LFS pointer version");
+    }
+    let oid = oid.context("Git LFS pointer is missing an oid line")?;
+    let size = size.context("Git LFS pointer is missing a size line")?;
+    Ok(LfsPointer { oid, size })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_valid_pointer() {
+        let pointer = parse_lfs_pointer(
+            "version https://git-lfs.github.com/spec/v1\n\
+             oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17c6644\n\
+             size 12345\n",
+        )
+        .expect("valid pointer should parse");
+        assert_eq!(
+            pointer.oid,
+            "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17c6644"
+        );
+        assert_eq!(pointer.size, 12345);
+    }
+
+    #[test]
+    fn rejects_wrong_version() {
+        let err = parse_lfs_pointer(
+            "version https://git-lfs.github.com/spec/v2\n\
+             oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17c6644\n\
+             size 12345\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unsupported or missing"));
+    }
+}
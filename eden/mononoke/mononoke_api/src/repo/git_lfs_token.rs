@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Short-lived, HMAC-signed capability tokens scoped to a single Git LFS or
+//! git object transfer.
+//!
+//! The git object upload path doesn't authenticate its callers; the SCS
+//! server only gets TLS at the connection level. This module gives the
+//! batch API a way to hand out narrowly-scoped, time-bounded upload and
+//! download rights (as the `Authorization` header of a batch `href`)
+//! instead of relying on full repo access.
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::Hmac;
+use hmac::Mac;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::repo::LfsOperation;
+
+/// A fixed-size digest identifying a git object or Git LFS object. For git
+/// objects this is a SHA-1 `ObjectId`; for Git LFS objects it is the raw
+/// bytes of the SHA-256 oid.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Oid(Vec<u8>);
+
+impl Oid {
+    pub fn from_hex(hex: &str) -> Result<Oid> {
+        Ok(Oid(hex::decode(hex).context("invalid oid hex")?))
+    }
+}
+
+/// The operation a token grants. `BatchApi` covers the batch endpoint call
+/// itself; `Upload`/`Download` grant, respectively, storing or fetching the
+/// bytes of a single, specific object once the batch response has pointed
+/// the client at it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SpecificClaim {
+    BatchApi(LfsOperation),
+    Upload(Oid),
+    Download(Oid),
+}
+
+/// A capability claim: the repo it is scoped to, what it grants, and when
+/// it stops being valid.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Claim {
+    pub repo: String,
+    pub specific: SpecificClaim,
+    pub expires_at: u64,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Serializes a `Claim` into the exact byte sequence that gets HMAC-signed:
+/// `repo || 0x00 || tag byte || oid-or-operation || big-endian expiry`.
+fn claim_bytes(claim: &Claim) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(claim.repo.as_bytes());
+    bytes.push(0); // separates the repo name from the fixed-layout fields below
+    match &claim.specific {
+        SpecificClaim::BatchApi(operation) => {
+            bytes.push(1);
+            bytes.push(match operation {
+                LfsOperation::Upload => 0,
+                LfsOperation::Download => 1,
+            });
+        }
+        SpecificClaim::Upload(oid) => {
+            bytes.push(2);
+            bytes.extend_from_slice(&oid.0);
+        }
+        SpecificClaim::Download(oid) => {
+            bytes.push(3);
+            bytes.extend_from_slice(&oid.0);
+        }
+    }
+    bytes.extend_from_slice(&claim.expires_at.to_be_bytes());
+    bytes
+}
+
+/// Mint a token for `claim`, signed with `secret`. The result is of the form
+/// `base64(claim_bytes) "." base64(hmac_tag)` and is suitable for use
+/// directly as an `Authorization` header value.
+pub fn mint_token(secret: &[u8], claim: &Claim) -> Result<String> {
+    let bytes = claim_bytes(claim);
+    let mut mac = HmacSha256::new_from_slice(secret).context("invalid HMAC secret length")?;
+    mac.update(&bytes);
+    let tag = mac.finalize().into_bytes();
+    Ok(format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(&bytes),
+        URL_SAFE_NO_PAD.encode(tag)
+    ))
+}
+
+/// Verify that `token` was minted with `secret` for `expected_repo`, is not
+/// expired, and grants `expected`. Rejects on any mismatch, using a
+/// constant-time comparison for the HMAC tag so the verification
+/// side-channel can't be used to forge a token byte-by-byte.
+pub fn verify_token(
+    secret: &[u8],
+    token: &str,
+    expected_repo: &str,
+    expected: &SpecificClaim,
+) -> Result<()> {
+    let (claim_b64, tag_b64) = token
+        .split_once('.')
+        .context("malformed token: missing '.' separator")?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(claim_b64)
+        .context("malformed token: invalid claim encoding")?;
+    let tag = URL_SAFE_NO_PAD
+        .decode(tag_b64)
+        .context("malformed token: invalid tag encoding")?;
+
+    let mut mac = HmacSha256::new_from_slice(secret).context("invalid HMAC secret length")?;
+    mac.update(&bytes);
+    let expected_tag = mac.finalize().into_bytes();
+    if expected_tag.ct_eq(&tag).unwrap_u8() != 1 {
+        bail!("token signature verification failed");
+    }
+
+    let claim = parse_claim_bytes(&bytes)?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("system clock is before the unix epoch")?
+        .as_secs();
+    if now > claim.expires_at {
+        bail!("token has expired");
+    }
+    if claim.repo != expected_repo {
+        bail!("token is not scoped to this repo");
+    }
+    if &claim.specific != expected {
+        bail!("token does not grant the requested operation");
+    }
+    Ok(())
+}
+
+fn parse_claim_bytes(bytes: &[u8]) -> Result<Claim> {
+    let separator = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .context("malformed token: missing repo separator")?;
+    let repo = String::from_utf8(bytes[..separator].to_vec()).context("malformed token: repo")?;
+    let rest = &bytes[separator + 1..];
+    let (tag, rest) = rest.split_first().context("malformed token: missing tag byte")?;
+    let (specific, rest) = match tag {
+        1 => {
+            let (op, rest) = rest.split_first().context("malformed token: missing operation")?;
+            let operation = match op {
+                0 => LfsOperation::Upload,
+                1 => LfsOperation::Download,
+                _ => bail!("malformed token: unknown operation tag"),
+            };
+            (SpecificClaim::BatchApi(operation), rest)
+        }
+        2 => {
+            if rest.len() < 8 {
+                bail!("malformed token: truncated oid");
+            }
+            let (oid, rest) = rest.split_at(rest.len() - 8);
+            (SpecificClaim::Upload(Oid(oid.to_vec())), rest)
+        }
+        3 => {
+            if rest.len() < 8 {
+                bail!("malformed token: truncated oid");
+            }
+            let (oid, rest) = rest.split_at(rest.len() - 8);
+            (SpecificClaim::Download(Oid(oid.to_vec())), rest)
+        }
+        _ => bail!("malformed token: unknown claim tag"),
+    };
+    if rest.len() != 8 {
+        bail!("malformed token: expiry is not 8 bytes");
+    }
+    let mut expiry_bytes = [0u8; 8];
+    expiry_bytes.copy_from_slice(rest);
+    Ok(Claim {
+        repo,
+        specific,
+        expires_at: u64::from_be_bytes(expiry_bytes),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mint_and_verify_round_trip() {
+        let secret = b"test-secret";
+        let claim = Claim {
+            repo: "repo1".to_string(),
+            specific: SpecificClaim::BatchApi(LfsOperation::Download),
+            expires_at: u64::MAX,
+        };
+        let token = mint_token(secret, &claim).expect("mint should succeed");
+        verify_token(
+            secret,
+            &token,
+            "repo1",
+            &SpecificClaim::BatchApi(LfsOperation::Download),
+        )
+        .expect("verification should succeed");
+    }
+
+    #[test]
+    fn rejects_wrong_secret() {
+        let claim = Claim {
+            repo: "repo1".to_string(),
+            specific: SpecificClaim::BatchApi(LfsOperation::Download),
+            expires_at: u64::MAX,
+        };
+        let token = mint_token(b"secret-a", &claim).expect("mint should succeed");
+        let result = verify_token(
+            b"secret-b",
+            &token,
+            "repo1",
+            &SpecificClaim::BatchApi(LfsOperation::Download),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let claim = Claim {
+            repo: "repo1".to_string(),
+            specific: SpecificClaim::BatchApi(LfsOperation::Download),
+            expires_at: 0,
+        };
+        let secret = b"test-secret";
+        let token = mint_token(secret, &claim).expect("mint should succeed");
+        let result = verify_token(
+            secret,
+            &token,
+            "repo1",
+            &SpecificClaim::BatchApi(LfsOperation::Download),
+        );
+        assert!(result.unwrap_err().to_string().contains("expired"));
+    }
+
+    #[test]
+    fn rejects_mismatched_claim() {
+        let secret = b"test-secret";
+        let claim = Claim {
+            repo: "repo1".to_string(),
+            specific: SpecificClaim::BatchApi(LfsOperation::Upload),
+            expires_at: u64::MAX,
+        };
+        let token = mint_token(secret, &claim).expect("mint should succeed");
+        let result = verify_token(
+            secret,
+            &token,
+            "repo1",
+            &SpecificClaim::BatchApi(LfsOperation::Download),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("does not grant"));
+    }
+
+    #[test]
+    fn rejects_token_scoped_to_a_different_repo() {
+        let secret = b"test-secret";
+        let claim = Claim {
+            repo: "repo1".to_string(),
+            specific: SpecificClaim::BatchApi(LfsOperation::Download),
+            expires_at: u64::MAX,
+        };
+        let token = mint_token(secret, &claim).expect("mint should succeed");
+        let result = verify_token(
+            secret,
+            &token,
+            "repo2",
+            &SpecificClaim::BatchApi(LfsOperation::Download),
+        );
+        assert!(result.unwrap_err().to_string().contains("not scoped"));
+    }
+}
@@ -0,0 +1,435 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Signed-commit policy enforcement at git object ingestion time.
+//!
+//! `upload_git_object` never inspects the `pgp_signature` a `Tag` or commit
+//! may carry. This module adds an optional layer that checks such a
+//! signature against a configured set of trusted keys, yields a verdict
+//! (good / bad / unknown-signer / unsigned), and applies a policy chosen by
+//! the caller per upload: enforce (reject anything but a good signature) or
+//! permissive (accept the object). The verdict is returned to the caller;
+//! this module does not store it.
+//!
+//! `verify_signature` checks the ed25519 SSHSIG signatures git produces via
+//! `git commit -S`/`git tag -s` with `gpg.format = ssh` (`ssh-keygen -Y
+//! sign`'s own on-disk format, documented in OpenSSH's `PROTOCOL.sshsig`).
+//! Other signature formats (full OpenPGP) can be added as this function
+//! grows additional framing branches, without touching the policy logic in
+//! `enforce_policy`.
+
+use std::collections::HashMap;
+
+use anyhow::bail;
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::Signature;
+use ed25519_dalek::Verifier;
+use ed25519_dalek::VerifyingKey;
+use git_hash::oid;
+use sha2::Digest;
+use sha2::Sha256;
+use sha2::Sha512;
+
+use crate::RepoContext;
+
+/// The outcome of checking a signed object against the trusted key set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignatureVerdict {
+    /// The signature is present and verifies against a trusted key.
+    Good,
+    /// The signature is present but does not verify.
+    Bad,
+    /// The signature is present but its signing key isn't in the trusted set.
+    UnknownSigner,
+    /// The object carries no signature at all.
+    Unsigned,
+}
+
+/// How a protected ref should react to a non-`Good` verdict.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignaturePolicy {
+    /// Reject unsigned or bad-signature objects outright.
+    Enforce,
+    /// Accept any object; the verdict is still returned to the caller.
+    Permissive,
+}
+
+/// A signature block's expected framing: the PEM-like armor `ssh-keygen -Y
+/// sign` (and `git commit -S`/`git tag -s` with `gpg.format = ssh`) writes,
+/// wrapping a base64-encoded binary blob,
+/// `-----BEGIN SSH SIGNATURE-----\n<base64, line-wrapped>\n-----END SSH SIGNATURE-----\n`.
+const SSH_SIGNATURE_HEADER: &str = "-----BEGIN SSH SIGNATURE-----";
+const SSH_SIGNATURE_FOOTER: &str = "-----END SSH SIGNATURE-----";
+
+/// The magic preamble every decoded SSHSIG blob starts with, per OpenSSH's
+/// `PROTOCOL.sshsig`.
+const SSHSIG_MAGIC: &[u8] = b"SSHSIG";
+
+/// The only signature namespace this module accepts. `git` always signs
+/// commits/tags under the `git` namespace, so a signature made for any
+/// other purpose (even by a trusted key) must not verify as one.
+const GIT_NAMESPACE: &[u8] = b"git";
+
+/// The set of keys this repo trusts to sign tags/commits. An SSHSIG
+/// signature embeds the full signing public key rather than a key id, so
+/// lookup is by the raw key bytes; `name` is kept only as a human-readable
+/// label (akin to a principal in git's `allowed_signers` file format).
+#[derive(Clone, Default)]
+pub struct TrustedKeys {
+    keys: HashMap<String, VerifyingKey>,
+}
+
+impl TrustedKeys {
+    pub fn new() -> TrustedKeys {
+        TrustedKeys::default()
+    }
+
+    pub fn insert(&mut self, name: String, key: VerifyingKey) {
+        self.keys.insert(name, key);
+    }
+
+    fn contains(&self, key: &VerifyingKey) -> bool {
+        self.keys.values().any(|trusted| trusted.as_bytes() == key.as_bytes())
+    }
+}
+
+/// A cursor over an SSH wire-format byte blob (`uint32` lengths and
+/// length-prefixed `string`s, per RFC 4251 section 5 / `PROTOCOL.sshsig`).
+struct WireReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    fn new(data: &'a [u8]) -> WireReader<'a> {
+        WireReader { data, pos: 0 }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.read_bytes(4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_string(&mut self) -> Option<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.read_bytes(len)
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// An ed25519 SSHSIG signature, decoded from the base64 blob inside a
+/// `BEGIN/END SSH SIGNATURE` armor.
+struct SshSig {
+    public_key: VerifyingKey,
+    namespace: Vec<u8>,
+    hash_algorithm: Vec<u8>,
+    signature: Signature,
+}
+
+/// An SSH wire-format public key blob (`string keytype || string key`);
+/// only `ssh-ed25519` keys are supported.
+fn parse_ed25519_public_key(blob: &[u8]) -> Option<VerifyingKey> {
+    let mut reader = WireReader::new(blob);
+    if reader.read_string()? != b"ssh-ed25519" {
+        return None;
+    }
+    let key_bytes: [u8; 32] = reader.read_string()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&key_bytes).ok()
+}
+
+/// An SSH wire-format signature blob (`string sigtype || string sig`).
+fn parse_ed25519_signature(blob: &[u8]) -> Option<Signature> {
+    let mut reader = WireReader::new(blob);
+    if reader.read_string()? != b"ssh-ed25519" {
+        return None;
+    }
+    let sig_bytes: [u8; 64] = reader.read_string()?.try_into().ok()?;
+    Some(Signature::from_bytes(&sig_bytes))
+}
+
+/// Parse the binary SSHSIG blob: `MAGIC_PREAMBLE || uint32 version ||
+/// string publickey || string namespace || string reserved ||
+/// string hash_algorithm || string signature`.
+fn parse_sshsig(raw: &[u8]) -> Option<SshSig> {
+    let mut reader = WireReader::new(raw);
+    if reader.read_bytes(SSHSIG_MAGIC.len())? != SSHSIG_MAGIC {
+        return None;
+    }
+    if reader.read_u32()? != 1 {
+        return None;
+    }
+    let public_key = parse_ed25519_public_key(reader.read_string()?)?;
+    let namespace = reader.read_string()?.to_vec();
+    let _reserved = reader.read_string()?;
+    let hash_algorithm = reader.read_string()?.to_vec();
+    let signature = parse_ed25519_signature(reader.read_string()?)?;
+
+    Some(SshSig {
+        public_key,
+        namespace,
+        hash_algorithm,
+        signature,
+    })
+}
+
+/// Verify `signature` (the raw text of a tag/commit's signature field, if
+/// any) as a signature over `message` (the signed content preceding it),
+/// returning the resulting verdict.
+pub fn verify_signature(
+    message: &[u8],
+    signature: Option<&str>,
+    trusted: &TrustedKeys,
+) -> Result<SignatureVerdict> {
+    let signature = match signature {
+        Some(signature) => signature,
+        None => return Ok(SignatureVerdict::Unsigned),
+    };
+
+    let body = match signature
+        .trim()
+        .strip_prefix(SSH_SIGNATURE_HEADER)
+        .and_then(|rest| rest.strip_suffix(SSH_SIGNATURE_FOOTER))
+    {
+        Some(body) => body,
+        None => return Ok(SignatureVerdict::Bad),
+    };
+
+    // The armor line-wraps its base64 payload; rejoin before decoding.
+    let encoded: String = body.split_whitespace().collect();
+    let raw = match STANDARD.decode(encoded) {
+        Ok(raw) => raw,
+        Err(_) => return Ok(SignatureVerdict::Bad),
+    };
+
+    let sig = match parse_sshsig(&raw) {
+        Some(sig) => sig,
+        None => return Ok(SignatureVerdict::Bad),
+    };
+
+    if sig.namespace != GIT_NAMESPACE {
+        return Ok(SignatureVerdict::Bad);
+    }
+
+    let message_hash: Vec<u8> = match sig.hash_algorithm.as_slice() {
+        b"sha256" => Sha256::digest(message).to_vec(),
+        b"sha512" => Sha512::digest(message).to_vec(),
+        _ => return Ok(SignatureVerdict::Bad),
+    };
+
+    if !trusted.contains(&sig.public_key) {
+        return Ok(SignatureVerdict::UnknownSigner);
+    }
+
+    // The actual signed payload isn't `message` itself, but this wrapper
+    // structure around its hash; see `PROTOCOL.sshsig`.
+    let mut signed_data = Vec::new();
+    signed_data.extend_from_slice(SSHSIG_MAGIC);
+    write_string(&mut signed_data, &sig.namespace);
+    write_string(&mut signed_data, b""); // reserved
+    write_string(&mut signed_data, &sig.hash_algorithm);
+    write_string(&mut signed_data, &message_hash);
+
+    Ok(
+        if sig.public_key.verify(&signed_data, &sig.signature).is_ok() {
+            SignatureVerdict::Good
+        } else {
+            SignatureVerdict::Bad
+        },
+    )
+}
+
+/// Apply `policy` to a verdict, returning an error describing the rejection
+/// when the policy is `Enforce` and the object isn't `Good`.
+pub fn enforce_policy(verdict: SignatureVerdict, policy: SignaturePolicy) -> Result<()> {
+    if policy == SignaturePolicy::Permissive {
+        return Ok(());
+    }
+    match verdict {
+        SignatureVerdict::Good => Ok(()),
+        SignatureVerdict::Bad => bail!("Invalid git object data: signature did not verify"),
+        SignatureVerdict::UnknownSigner => {
+            bail!("Invalid git object data: signature from an untrusted key")
+        }
+        SignatureVerdict::Unsigned => bail!("Invalid git object data: object is unsigned"),
+    }
+}
+
+impl RepoContext {
+    /// Upload a tag or commit object the same way `upload_git_object` does,
+    /// but first check its signature against `trusted` and apply `policy`.
+    /// Returns the resulting verdict so that callers can record it
+    /// alongside the object even in permissive mode.
+    pub async fn upload_git_object_with_signature_policy(
+        &self,
+        hash: &oid,
+        bytes: Vec<u8>,
+        signed_message: &[u8],
+        signature: Option<&str>,
+        trusted: &TrustedKeys,
+        policy: SignaturePolicy,
+    ) -> Result<SignatureVerdict> {
+        let verdict = verify_signature(signed_message, signature, trusted)?;
+        enforce_policy(verdict, policy)?;
+        self.upload_git_object(hash, bytes).await?;
+        Ok(verdict)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ed25519_dalek::Signer;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    /// Build a `BEGIN/END SSH SIGNATURE` armored blob the way `ssh-keygen
+    /// -Y sign` would for `message`, under the given `namespace`/
+    /// `hash_algorithm`, so tests exercise the real SSHSIG framing rather
+    /// than a simplified stand-in.
+    fn signed_block(
+        signing_key: &SigningKey,
+        namespace: &[u8],
+        hash_algorithm: &[u8],
+        message: &[u8],
+    ) -> String {
+        let message_hash = match hash_algorithm {
+            b"sha256" => Sha256::digest(message).to_vec(),
+            b"sha512" => Sha512::digest(message).to_vec(),
+            other => panic!("unsupported test hash algorithm {:?}", other),
+        };
+
+        let mut signed_data = Vec::new();
+        signed_data.extend_from_slice(SSHSIG_MAGIC);
+        write_string(&mut signed_data, namespace);
+        write_string(&mut signed_data, b"");
+        write_string(&mut signed_data, hash_algorithm);
+        write_string(&mut signed_data, &message_hash);
+        let signature = signing_key.sign(&signed_data);
+
+        let mut public_key_blob = Vec::new();
+        write_string(&mut public_key_blob, b"ssh-ed25519");
+        write_string(&mut public_key_blob, signing_key.verifying_key().as_bytes());
+
+        let mut signature_blob = Vec::new();
+        write_string(&mut signature_blob, b"ssh-ed25519");
+        write_string(&mut signature_blob, &signature.to_bytes());
+
+        let mut raw = Vec::new();
+        raw.extend_from_slice(SSHSIG_MAGIC);
+        raw.extend_from_slice(&1u32.to_be_bytes());
+        write_string(&mut raw, &public_key_blob);
+        write_string(&mut raw, namespace);
+        write_string(&mut raw, b"");
+        write_string(&mut raw, hash_algorithm);
+        write_string(&mut raw, &signature_blob);
+
+        format!(
+            "{}\n{}\n{}\n",
+            SSH_SIGNATURE_HEADER,
+            STANDARD.encode(raw),
+            SSH_SIGNATURE_FOOTER
+        )
+    }
+
+    #[test]
+    fn verifies_good_signature() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let mut trusted = TrustedKeys::new();
+        trusted.insert("key1".to_string(), signing_key.verifying_key());
+
+        let message = b"tag contents";
+        let block = signed_block(&signing_key, GIT_NAMESPACE, b"sha256", message);
+        let verdict = verify_signature(message, Some(&block), &trusted).unwrap();
+        assert_eq!(verdict, SignatureVerdict::Good);
+    }
+
+    #[test]
+    fn verifies_good_signature_with_sha512() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let mut trusted = TrustedKeys::new();
+        trusted.insert("key1".to_string(), signing_key.verifying_key());
+
+        let message = b"tag contents";
+        let block = signed_block(&signing_key, GIT_NAMESPACE, b"sha512", message);
+        let verdict = verify_signature(message, Some(&block), &trusted).unwrap();
+        assert_eq!(verdict, SignatureVerdict::Good);
+    }
+
+    #[test]
+    fn flags_unknown_signer() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let trusted = TrustedKeys::new();
+
+        let message = b"tag contents";
+        let block = signed_block(&signing_key, GIT_NAMESPACE, b"sha256", message);
+        let verdict = verify_signature(message, Some(&block), &trusted).unwrap();
+        assert_eq!(verdict, SignatureVerdict::UnknownSigner);
+    }
+
+    #[test]
+    fn flags_wrong_namespace_as_bad() {
+        // A signature made for a different purpose (e.g. `file` namespace)
+        // must not verify as a git signature, even from a trusted key.
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let mut trusted = TrustedKeys::new();
+        trusted.insert("key1".to_string(), signing_key.verifying_key());
+
+        let message = b"tag contents";
+        let block = signed_block(&signing_key, b"file", b"sha256", message);
+        let verdict = verify_signature(message, Some(&block), &trusted).unwrap();
+        assert_eq!(verdict, SignatureVerdict::Bad);
+    }
+
+    #[test]
+    fn flags_unsigned() {
+        let trusted = TrustedKeys::new();
+        let verdict = verify_signature(b"tag contents", None, &trusted).unwrap();
+        assert_eq!(verdict, SignatureVerdict::Unsigned);
+    }
+
+    #[test]
+    fn flags_malformed_signature_as_bad_rather_than_erroring() {
+        let trusted = TrustedKeys::new();
+        let verdict = verify_signature(b"tag contents", Some("not a signature block"), &trusted)
+            .expect("malformed signatures should verify to a verdict, not an error");
+        assert_eq!(verdict, SignatureVerdict::Bad);
+    }
+
+    #[test]
+    fn permissive_accepts_malformed_signature_but_records_bad() {
+        let trusted = TrustedKeys::new();
+        let verdict = verify_signature(b"tag contents", Some("not a signature block"), &trusted)
+            .unwrap();
+        assert_eq!(verdict, SignatureVerdict::Bad);
+        assert!(enforce_policy(verdict, SignaturePolicy::Permissive).is_ok());
+    }
+
+    #[test]
+    fn enforce_rejects_unsigned() {
+        let result = enforce_policy(SignatureVerdict::Unsigned, SignaturePolicy::Enforce);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn permissive_accepts_unsigned() {
+        let result = enforce_policy(SignatureVerdict::Unsigned, SignaturePolicy::Permissive);
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,29 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod git_bundle;
+mod git_lfs;
+pub(crate) mod git_lfs_token;
+mod git_signature;
+
+pub use git_bundle::parse_bundle_header;
+pub use git_bundle::BundleHeader;
+pub use git_lfs::parse_lfs_pointer;
+pub use git_lfs::LfsAction;
+pub use git_lfs::LfsBatchObject;
+pub use git_lfs::LfsBatchRequest;
+pub use git_lfs::LfsObject;
+pub use git_lfs::LfsOperation;
+pub use git_lfs::LfsPointer;
+pub use git_lfs_token::Claim;
+pub use git_lfs_token::Oid;
+pub use git_lfs_token::SpecificClaim;
+pub use git_signature::enforce_policy;
+pub use git_signature::verify_signature;
+pub use git_signature::SignaturePolicy;
+pub use git_signature::SignatureVerdict;
+pub use git_signature::TrustedKeys;
@@ -0,0 +1,25 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Shared fixtures for the `mononoke_api` integration-style tests under
+//! `src/test/`.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::CoreContext;
+use crate::Repo;
+use crate::RepoContext;
+
+/// Build a `RepoContext` backed by a freshly created, empty test repo.
+pub(crate) async fn init_repo(ctx: &CoreContext) -> Result<RepoContext> {
+    let blob_repo = test_repo_factory::build_empty(ctx.fb)?;
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo_context = RepoContext::new_test(ctx.clone(), Arc::new(repo)).await?;
+    Ok(repo_context)
+}
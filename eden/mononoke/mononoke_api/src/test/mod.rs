@@ -0,0 +1,15 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#[cfg(test)]
+mod fixtures;
+#[cfg(test)]
+mod test_git;
+#[cfg(test)]
+mod test_git_bundle;
+#[cfg(test)]
+mod test_git_lfs;
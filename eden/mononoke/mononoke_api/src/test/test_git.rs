@@ -6,7 +6,6 @@
  */
 
 use std::io::Write;
-use std::sync::Arc;
 
 use anyhow::Result;
 use blobstore::Blobstore;
@@ -17,16 +16,8 @@ use git_hash::ObjectId;
 use git_object::Tag;
 use git_object::WriteTo;
 
+use crate::test::fixtures::init_repo;
 use crate::CoreContext;
-use crate::Repo;
-use crate::RepoContext;
-
-async fn init_repo(ctx: &CoreContext) -> Result<RepoContext> {
-    let blob_repo = test_repo_factory::build_empty(ctx.fb)?;
-    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
-    let repo_context = RepoContext::new_test(ctx.clone(), Arc::new(repo)).await?;
-    Ok(repo_context)
-}
 
 #[fbinit::test]
 /// Validate the basic git upload object functionality works.
@@ -0,0 +1,35 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use bytes::Bytes;
+use fbinit::FacebookInit;
+
+use crate::test::fixtures::init_repo;
+use crate::CoreContext;
+
+#[fbinit::test]
+/// A bundle whose prerequisite commit isn't already stored in this repo
+/// must be rejected before any of its objects are ingested.
+async fn rejects_missing_prerequisite(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo_ctx = init_repo(&ctx).await?;
+
+    let mut bundle = b"# v2 git bundle\n\
+-1111111111111111111111111111111111111111\n\
+\n"
+    .to_vec();
+    bundle.extend_from_slice(b"PACK\x00\x00\x00\x02\x00\x00\x00\x00");
+
+    let output = repo_ctx.upload_git_bundle(Bytes::from(bundle)).await;
+    assert!(output.is_err());
+    assert!(output
+        .unwrap_err()
+        .to_string()
+        .contains("is not present in this repo"));
+    Ok(())
+}
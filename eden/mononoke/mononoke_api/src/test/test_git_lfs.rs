@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use bytes::Bytes;
+use fbinit::FacebookInit;
+
+use crate::repo::LfsBatchRequest;
+use crate::repo::LfsObject;
+use crate::repo::LfsOperation;
+use crate::test::fixtures::init_repo;
+use crate::CoreContext;
+use crate::RepoContext;
+
+const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17c6644";
+
+const SECRET: &[u8] = b"test-secret";
+
+/// Mint the upload token `upload_git_lfs_object` expects, the same way a
+/// real client would obtain one from the batch API first.
+fn upload_token(repo_ctx: &RepoContext, oid: &str) -> Result<String> {
+    let response = repo_ctx.git_lfs_batch(
+        SECRET,
+        LfsBatchRequest {
+            operation: LfsOperation::Upload,
+            objects: vec![LfsObject {
+                oid: oid.to_string(),
+                size: 0,
+            }],
+        },
+    )?;
+    Ok(response.objects[0]
+        .actions
+        .get("upload")
+        .expect("upload action should be present")
+        .header
+        .get("Authorization")
+        .expect("Authorization header should be present")
+        .clone())
+}
+
+#[fbinit::test]
+/// Validate that uploading a Git LFS object whose content doesn't hash to
+/// its oid is rejected.
+async fn upload_lfs_object_rejects_hash_mismatch(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo_ctx = init_repo(&ctx).await?;
+    let token = upload_token(&repo_ctx, OID)?;
+
+    // OID does not match the hash of the (empty) content, so upload should
+    // fail with the same style of hash-mismatch error as `upload_git_object`.
+    let content = Bytes::from_static(b"");
+    let output = repo_ctx
+        .upload_git_lfs_object(SECRET, OID, &token, content.clone())
+        .await;
+    assert!(output.is_err());
+    assert!(output
+        .unwrap_err()
+        .to_string()
+        .contains("does not match hash of bytes"));
+    Ok(())
+}
+
+#[fbinit::test]
+/// Validate that an upload token can't be replayed against the download
+/// endpoint.
+async fn upload_token_is_not_a_download_token(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo_ctx = init_repo(&ctx).await?;
+    let token = upload_token(&repo_ctx, OID)?;
+
+    let output = repo_ctx.download_git_lfs_object(SECRET, OID, &token).await;
+    assert!(output.is_err());
+    Ok(())
+}
+
+#[fbinit::test]
+/// Validate that the batch API returns an action per requested object.
+async fn batch_api_returns_actions(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo_ctx = init_repo(&ctx).await?;
+
+    let response = repo_ctx.git_lfs_batch(
+        b"test-secret",
+        LfsBatchRequest {
+            operation: LfsOperation::Download,
+            objects: vec![LfsObject {
+                oid: OID.to_string(),
+                size: 12345,
+            }],
+        },
+    )?;
+    assert_eq!(response.objects.len(), 1);
+    let action = response.objects[0]
+        .actions
+        .get("download")
+        .expect("download action should be present");
+    assert!(action.header.contains_key("Authorization"));
+    Ok(())
+}
@@ -7,13 +7,20 @@
 
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::Result;
 use log::info;
+use log::warn;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::io::AsyncReadExt;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::BufReader;
 use tokio::net::TcpListener;
+use tokio::net::UnixListener;
 
 /// Set of supported commands
 /// All unknown commands will be ignored
@@ -33,33 +40,63 @@ pub struct CommandData {
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+impl CommandData {
+    /// The `token` field, if the command envelope carried one. Present
+    /// alongside whatever other fields the command needs, since `extra`
+    /// flattens all unrecognized JSON keys together.
+    fn token(&self) -> Option<&str> {
+        self.extra.get("token").and_then(|value| value.as_str())
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Command(pub (CommandName, CommandData));
 
-/// Simple cross platform commands receiver working on top of Tcp Socket and json
-/// Expected commands are in json format
+/// Where a `ReceiverService` listens for incoming command connections.
+pub enum Endpoint {
+    Tcp(SocketAddr),
+    /// The socket is created with `0600` permissions, inside a `0700`
+    /// parent directory, so that only the owning user can connect to it.
+    Unix(PathBuf),
+}
+
+/// Simple cross platform commands receiver working on top of a socket and
+/// json, either a Tcp socket or (preferred, since it is restricted to the
+/// owning user by filesystem permissions) a Unix domain socket.
+/// Expected commands are in json format, one per line.
 /// Example: ["commitcloud::restart_subscriptions", {"foo": "bar"}]
 /// Example to test: echo '["commitcloud::restart_subscriptions", {}]' | nc localhost 15432
 /// with_actions builder is used to configure callbacks
+/// with_token restricts accepted commands to ones carrying a matching
+/// `token` field, so that other local processes can't drive this service
 /// The serve function starts the service
-
-pub struct TcpReceiverService {
-    port: u16,
+pub struct ReceiverService {
+    endpoint: Endpoint,
     actions: HashMap<CommandName, Box<dyn Fn() + Send>>,
+    token: Option<String>,
 }
 
-impl TcpReceiverService {
-    pub fn new(port: u16) -> TcpReceiverService {
-        TcpReceiverService {
-            port,
+impl ReceiverService {
+    pub fn tcp(port: u16) -> ReceiverService {
+        ReceiverService {
+            endpoint: Endpoint::Tcp(SocketAddr::from(([127, 0, 0, 1], port))),
             actions: HashMap::new(),
+            token: None,
+        }
+    }
+
+    pub fn unix(path: impl Into<PathBuf>) -> ReceiverService {
+        ReceiverService {
+            endpoint: Endpoint::Unix(path.into()),
+            actions: HashMap::new(),
+            token: None,
         }
     }
 
     pub fn with_actions(
         mut self,
         actions: HashMap<CommandName, Box<dyn Fn() + Send>>,
-    ) -> TcpReceiverService {
+    ) -> ReceiverService {
         self.actions = self
             .actions
             .into_iter()
@@ -68,27 +105,109 @@ impl TcpReceiverService {
         self
     }
 
+    /// Require every command to carry a `token` field matching `token`,
+    /// logging and dropping any command that doesn't.
+    pub fn with_token(mut self, token: String) -> ReceiverService {
+        self.token = Some(token);
+        self
+    }
+
     pub fn serve(self) -> Result<tokio::task::JoinHandle<Result<()>>> {
         Ok(tokio::task::spawn(async move {
-            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], self.port))).await?;
-            info!("Starting CommitCloud TcpReceiverService");
-            info!("Listening on port {}", self.port);
-            loop {
-                let (mut socket, _) = listener.accept().await?;
-                let mut buf = Vec::new();
-                let bytes_read = socket.read_to_end(&mut buf).await?;
-
-                let command: Command = serde_json::from_slice(&buf[..bytes_read])?;
-                let command_name = serde_json::to_string(&(command.0).0)
-                    .ok()
-                    .unwrap_or("unknown".into());
-                info!("Received {} command", command_name);
-                if let Some(action) = self.actions.get(&((command.0).0)) {
-                    action();
-                } else {
-                    info!("No actions found for {}", command_name);
+            info!("Starting CommitCloud ReceiverService");
+            match self.endpoint {
+                Endpoint::Tcp(addr) => {
+                    let listener = TcpListener::bind(addr).await?;
+                    info!("Listening on {}", addr);
+                    loop {
+                        let (socket, _) = listener.accept().await?;
+                        handle_connection(socket, &self.actions, self.token.as_deref()).await;
+                    }
+                }
+                Endpoint::Unix(ref path) => {
+                    let listener = bind_unix_socket(path)?;
+                    info!("Listening on {}", path.display());
+                    loop {
+                        let (socket, _) = listener.accept().await?;
+                        handle_connection(socket, &self.actions, self.token.as_deref()).await;
+                    }
                 }
             }
         }))
     }
 }
+
+fn bind_unix_socket(path: &Path) -> Result<UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+
+    // `UnixListener::bind` has no way to choose the socket file's
+    // permissions directly (they're whatever the umask allows at bind
+    // time), so there's an unavoidable window between bind(2) succeeding
+    // and the `set_permissions` below landing. Close that window by making
+    // the *parent directory* private (0700) rather than relying on the
+    // socket file's own mode: with the directory untraversable by anyone
+    // else, the socket path can't be resolved by another user regardless of
+    // what the socket's momentary permissions are.
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+        std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Read newline-delimited JSON commands off a single connection until it is
+/// closed, dispatching each one (and logging + dropping it instead if it
+/// fails the configured token check).
+async fn handle_connection<S>(
+    socket: S,
+    actions: &HashMap<CommandName, Box<dyn Fn() + Send>>,
+    expected_token: Option<&str>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut lines = BufReader::new(socket).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(err) => {
+                warn!("Error reading from CommitCloud ReceiverService connection: {}", err);
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let command: Command = match serde_json::from_str(&line) {
+            Ok(command) => command,
+            Err(err) => {
+                warn!("Ignoring malformed CommitCloud command: {}", err);
+                continue;
+            }
+        };
+        let (name, data) = command.0;
+
+        if let Some(expected_token) = expected_token {
+            if data.token() != Some(expected_token) {
+                warn!("Dropping command with missing or incorrect token");
+                continue;
+            }
+        }
+
+        let command_name = serde_json::to_string(&name).ok().unwrap_or("unknown".into());
+        info!("Received {} command", command_name);
+        if let Some(action) = actions.get(&name) {
+            action();
+        } else {
+            info!("No actions found for {}", command_name);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) 2004-present, Facebook, Inc.
+// All Rights Reserved.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2 or any later version.
+
+//! Typed pushvar and config-value coercion.
+//!
+//! `is_hook_bypassed` used to compare pushvar values only as raw UTF-8
+//! strings, which makes numeric or boolean gates (e.g. bypass only if
+//! `EMERGENCY=1` or `PRIORITY>=3`) impossible. `HookBypass::Pushvar` can
+//! declare a `metaconfig_types::Conversion` to coerce both sides into;
+//! `evaluate_condition` then lets the configured value express a typed
+//! comparison such as `">=3"`, falling back to `None` (condition not met)
+//! on any parse failure rather than treating it as a hard config error.
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use metaconfig_types::Conversion;
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+enum TypedValue {
+    Bytes(Vec<u8>),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Timestamp(i64),
+}
+
+/// Coerce `raw` according to `conversion`, or `None` if it doesn't parse as
+/// that type.
+fn parse_typed(raw: &[u8], conversion: &Conversion) -> Option<TypedValue> {
+    match conversion {
+        Conversion::Bytes => Some(TypedValue::Bytes(raw.to_vec())),
+        Conversion::Integer => std::str::from_utf8(raw)
+            .ok()?
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .map(TypedValue::Integer),
+        Conversion::Float => std::str::from_utf8(raw)
+            .ok()?
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(TypedValue::Float),
+        Conversion::Boolean => match std::str::from_utf8(raw).ok()?.trim() {
+            "1" | "true" | "True" | "TRUE" => Some(TypedValue::Boolean(true)),
+            "0" | "false" | "False" | "FALSE" => Some(TypedValue::Boolean(false)),
+            _ => None,
+        },
+        Conversion::Timestamp => std::str::from_utf8(raw)
+            .ok()?
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .map(TypedValue::Timestamp),
+        Conversion::TimestampFmt(format) => {
+            let text = std::str::from_utf8(raw).ok()?;
+            // `format` may or may not include a timezone (`"%Y-%m-%d
+            // %H:%M:%S %z"` vs. plain `"%Y-%m-%d"`); `DateTime::parse_from_str`
+            // only accepts the former, and returns `Err` on the latter, so try
+            // the tz-less date/time and date-only parsers (treated as UTC)
+            // before giving up.
+            if let Ok(dt) = DateTime::parse_from_str(text, format) {
+                return Some(TypedValue::Timestamp(dt.timestamp()));
+            }
+            if let Ok(dt) = NaiveDateTime::parse_from_str(text, format) {
+                return Some(TypedValue::Timestamp(dt.timestamp()));
+            }
+            NaiveDate::parse_from_str(text, format)
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|dt| TypedValue::Timestamp(dt.timestamp()))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Operator {
+    Eq,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+const OPERATORS: &[(&str, Operator)] = &[
+    (">=", Operator::Ge),
+    ("<=", Operator::Le),
+    ("==", Operator::Eq),
+    (">", Operator::Gt),
+    ("<", Operator::Lt),
+];
+
+/// Evaluate a typed bypass condition of the form `<op><literal>` (e.g.
+/// `">=3"`, `"==1"`) against `raw`, coercing both sides through
+/// `conversion`. Returns `None` - condition not met - if the condition
+/// isn't in this form, or either side fails to parse under `conversion`.
+pub fn evaluate_condition(raw: &[u8], conversion: &Conversion, condition: &str) -> Option<bool> {
+    for (token, op) in OPERATORS {
+        if let Some(literal) = condition.strip_prefix(token) {
+            let actual = parse_typed(raw, conversion)?;
+            let expected = parse_typed(literal.as_bytes(), conversion)?;
+            return Some(match op {
+                Operator::Eq => actual == expected,
+                Operator::Ge => actual >= expected,
+                Operator::Le => actual <= expected,
+                Operator::Gt => actual > expected,
+                Operator::Lt => actual < expected,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn parses_known_conversions() {
+        assert_eq!(Conversion::from_str("int").unwrap(), Conversion::Integer);
+        assert_eq!(Conversion::from_str("integer").unwrap(), Conversion::Integer);
+        assert_eq!(Conversion::from_str("float").unwrap(), Conversion::Float);
+        assert_eq!(Conversion::from_str("bool").unwrap(), Conversion::Boolean);
+        assert_eq!(Conversion::from_str("timestamp").unwrap(), Conversion::Timestamp);
+        assert_eq!(
+            Conversion::from_str("timestamp:%Y-%m-%d").unwrap(),
+            Conversion::TimestampFmt("%Y-%m-%d".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_conversion() {
+        assert!(Conversion::from_str("nonsense").is_err());
+    }
+
+    #[test]
+    fn evaluates_integer_comparison() {
+        assert_eq!(evaluate_condition(b"5", &Conversion::Integer, ">=3"), Some(true));
+        assert_eq!(evaluate_condition(b"2", &Conversion::Integer, ">=3"), Some(false));
+    }
+
+    #[test]
+    fn evaluates_boolean_equality() {
+        assert_eq!(evaluate_condition(b"1", &Conversion::Boolean, "==1"), Some(true));
+        assert_eq!(evaluate_condition(b"0", &Conversion::Boolean, "==1"), Some(false));
+    }
+
+    #[test]
+    fn unparseable_condition_is_not_matched() {
+        assert_eq!(
+            evaluate_condition(b"not-a-number", &Conversion::Integer, ">=3"),
+            None
+        );
+        assert_eq!(
+            evaluate_condition(b"5", &Conversion::Integer, "plain-string"),
+            None
+        );
+    }
+
+    #[test]
+    fn evaluates_timestamp_format_comparison() {
+        // "%Y-%m-%d" has no timezone, which `DateTime::parse_from_str`
+        // can't parse on its own; this must still evaluate correctly.
+        let conversion = Conversion::TimestampFmt("%Y-%m-%d".to_string());
+        assert_eq!(
+            evaluate_condition(b"2024-06-01", &conversion, ">=2024-01-01"),
+            Some(true)
+        );
+        assert_eq!(
+            evaluate_condition(b"2023-06-01", &conversion, ">=2024-01-01"),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,31 @@
+// Copyright (c) 2004-present, Facebook, Inc.
+// All Rights Reserved.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2 or any later version.
+
+use mercurial_types::HgChangesetId;
+use mononoke_types::MPath;
+
+#[derive(Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "Hook not found: {}", _0)]
+    NoSuchHook(String),
+    #[fail(display = "Changeset not found: {}", _0)]
+    NoSuchChangeset(String),
+    #[fail(display = "No content for file {} in changeset {}", _1, _0)]
+    NoFileContent(HgChangesetId, MPath),
+    #[fail(display = "Invalid bypass regex pattern {:?}: {}", _0, _1)]
+    InvalidBypassRegex(String, String),
+    #[fail(display = "Unknown value conversion: {:?}", _0)]
+    UnknownConversion(String),
+    #[fail(display = "Invalid hook config {:?}: {}", _0, _1)]
+    InvalidHookConfig(String, String),
+    #[fail(display = "Hook config include cycle detected at {:?}", _0)]
+    HookConfigIncludeCycle(String),
+    #[fail(
+        display = "Reading a full changeset from the revlog store is not supported: {}",
+        _0
+    )]
+    UnsupportedRevlogOperation(String),
+}
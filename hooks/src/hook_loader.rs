@@ -0,0 +1,185 @@
+// Copyright (c) 2004-present, Facebook, Inc.
+// All Rights Reserved.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2 or any later version.
+
+//! Layered, INI-style hook configuration files.
+//!
+//! A hook config is made up of one or more files layered on top of each
+//! other: a file loaded later overrides keys set by an earlier one, and a
+//! `%unset key` line deletes a key seen so far in the section it appears
+//! in. A `%include path` line pulls in another file (resolved relative to
+//! the file doing the including) before continuing with the rest of the
+//! current file, so shared defaults can be factored out. This mirrors the
+//! layering rules of Mercurial's own config files.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use failure::Error;
+use metaconfig_types::Conversion;
+use metaconfig_types::HookBypass;
+use metaconfig_types::HookConfig;
+use metaconfig_types::UnknownConversion;
+use regex::Regex;
+
+use crate::errors::ErrorKind;
+
+lazy_static! {
+    static ref SECTION_LINE: Regex = Regex::new(r"^\[([^\[]+)\]").expect("valid regex");
+    static ref ITEM_LINE: Regex =
+        Regex::new(r"^([^=\s][^=]*?)\s*=\s*((.*\S)?)").expect("valid regex");
+    static ref CONTINUATION_LINE: Regex = Regex::new(r"^\s+(\S|\S.*\S)\s*$").expect("valid regex");
+    static ref COMMENT_OR_BLANK_LINE: Regex = Regex::new(r"^(;|#|\s*$)").expect("valid regex");
+    static ref UNSET_DIRECTIVE: Regex = Regex::new(r"^%unset\s+(\S+)").expect("valid regex");
+    static ref INCLUDE_DIRECTIVE: Regex = Regex::new(r"^%include\s+(\S.*\S)").expect("valid regex");
+}
+
+/// A section name paired with its key, e.g. hook name and config key.
+type SectionKey = (String, String);
+
+/// Parse `path` and every file it (transitively) `%include`s, applying
+/// layering rules, and return the merged `section -> key -> value` map.
+///
+/// `visited` guards against include cycles: attempting to (re-)load a path
+/// already on the current include chain is an error rather than an
+/// infinite loop.
+pub fn load_layered_config(path: &Path) -> Result<HashMap<String, HashMap<String, String>>, Error> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut visited = HashSet::new();
+    load_into(path, &mut sections, &mut visited)?;
+    Ok(sections)
+}
+
+fn load_into(
+    path: &Path,
+    sections: &mut HashMap<String, HashMap<String, String>>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), Error> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| ErrorKind::InvalidHookConfig(path.display().to_string(), e.to_string()))?;
+    if !visited.insert(canonical.clone()) {
+        return Err(ErrorKind::HookConfigIncludeCycle(path.display().to_string()).into());
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| ErrorKind::InvalidHookConfig(path.display().to_string(), e.to_string()))?;
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut current_section = String::new();
+    let mut current_key: Option<String> = None;
+
+    for line in contents.lines() {
+        if let Some(captures) = INCLUDE_DIRECTIVE.captures(line) {
+            let included = base_dir.join(&captures[1]);
+            load_into(&included, sections, visited)?;
+            current_key = None;
+            continue;
+        }
+        if let Some(captures) = UNSET_DIRECTIVE.captures(line) {
+            if let Some(section) = sections.get_mut(&current_section) {
+                section.remove(&captures[1]);
+            }
+            current_key = None;
+            continue;
+        }
+        if let Some(captures) = SECTION_LINE.captures(line) {
+            current_section = captures[1].trim().to_string();
+            current_key = None;
+            continue;
+        }
+        if COMMENT_OR_BLANK_LINE.is_match(line) {
+            continue;
+        }
+        if let (Some(key), Some(captures)) = (&current_key, CONTINUATION_LINE.captures(line)) {
+            let section = sections.entry(current_section.clone()).or_default();
+            let value = section.entry(key.clone()).or_default();
+            value.push('\n');
+            value.push_str(&captures[1]);
+            continue;
+        }
+        if let Some(captures) = ITEM_LINE.captures(line) {
+            let key = captures[1].trim().to_string();
+            let value = captures.get(2).map_or("", |m| m.as_str()).to_string();
+            sections
+                .entry(current_section.clone())
+                .or_default()
+                .insert(key.clone(), value);
+            current_key = Some(key);
+            continue;
+        }
+    }
+
+    visited.remove(&canonical);
+    Ok(())
+}
+
+/// Build the hook name to `HookConfig` mapping that `HookManager`
+/// registration consumes, from a layered set of config files rooted at
+/// `path`: one entry per section, keyed by the section name. Bookmark to
+/// hooks assignments are not read from these files.
+pub fn load_hook_configs(path: &Path) -> Result<HashMap<String, HookConfig>, Error> {
+    let sections = load_layered_config(path)?;
+    sections
+        .into_iter()
+        .map(|(hook_name, keys)| {
+            let config = hook_config_from_keys(&keys)?;
+            Ok((hook_name, config))
+        })
+        .collect()
+}
+
+/// `bypass_pushvar`'s value may optionally be paired with a
+/// `bypass_pushvar_conversion` key (e.g. `int`, `bool`, `timestamp:%Y-%m-%d`)
+/// declaring how to coerce both sides of the comparison before it's
+/// evaluated; without it, the pushvar is compared as a raw string.
+fn hook_config_from_keys(keys: &HashMap<String, String>) -> Result<HookConfig, Error> {
+    let bypass = if let Some(pattern) = keys.get("bypass_commit_message_regex") {
+        Some(HookBypass::CommitMessageRegex(pattern.clone()))
+    } else if let Some(message) = keys.get("bypass_commit_message") {
+        Some(HookBypass::CommitMessage(message.clone()))
+    } else if let Some(spec) = keys.get("bypass_pushvar_regex") {
+        let (name, pattern) = split_pushvar_spec(spec)?;
+        Some(HookBypass::PushvarRegex { name, pattern })
+    } else if let Some(spec) = keys.get("bypass_pushvar") {
+        let (name, value) = split_pushvar_spec(spec)?;
+        let conversion = keys
+            .get("bypass_pushvar_conversion")
+            .map(|c| Conversion::from_str(c))
+            .transpose()
+            .map_err(|UnknownConversion(s)| ErrorKind::UnknownConversion(s))?;
+        Some(HookBypass::Pushvar {
+            name,
+            value,
+            conversion,
+        })
+    } else {
+        None
+    };
+
+    Ok(HookConfig {
+        bypass,
+        ..Default::default()
+    })
+}
+
+/// Split a `"NAME=VALUE"` (or `"NAME:PATTERN"`) pushvar spec into its two
+/// halves.
+fn split_pushvar_spec(spec: &str) -> Result<(String, String), Error> {
+    let separator = spec.find(['=', ':']).ok_or_else(|| {
+        ErrorKind::InvalidHookConfig(
+            spec.to_string(),
+            "expected NAME=VALUE or NAME:PATTERN".to_string(),
+        )
+    })?;
+    Ok((
+        spec[..separator].to_string(),
+        spec[separator + 1..].to_string(),
+    ))
+}
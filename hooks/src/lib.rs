@@ -33,6 +33,7 @@ extern crate fixtures;
 extern crate futures;
 #[macro_use]
 extern crate futures_ext;
+extern crate hg_parser;
 extern crate hlua;
 extern crate hlua_futures;
 #[macro_use]
@@ -55,9 +56,11 @@ extern crate context;
 extern crate srclient;
 extern crate thrift;
 
+mod conversion;
 pub mod errors;
 mod facebook;
 pub mod hook_loader;
+pub mod revlog_store;
 pub mod lua_hook;
 pub mod rust_hook;
 
@@ -69,11 +72,12 @@ use bytes::Bytes;
 use context::CoreContext;
 pub use errors::*;
 use failure::{Error, FutureFailureErrorExt};
-use futures::{failed, finished, Future, IntoFuture};
-use futures_ext::{BoxFuture, FutureExt};
+use futures::{failed, finished, stream, Future, IntoFuture};
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
 use mercurial_types::{manifest_utils::EntryStatus, Changeset, HgChangesetId, HgParents, MPath};
 use metaconfig_types::{HookBypass, HookConfig, HookManagerParams};
 use mononoke_types::FileType;
+use regex::bytes::Regex;
 use slog::Logger;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
@@ -86,6 +90,41 @@ type ChangesetHooks = HashMap<String, (Arc<Hook<HookChangeset>>, HookConfig)>;
 type FileHooks = Arc<Mutex<HashMap<String, (Arc<Hook<HookFile>>, HookConfig)>>>;
 type Cache = Asyncmemo<HookCacheFiller>;
 
+lazy_static! {
+    /// Compiled bypass regexes, keyed by their source pattern, so that a
+    /// pattern shared by many changesets (or seen again on hook
+    /// re-registration) is only ever compiled once.
+    static ref BYPASS_REGEX_CACHE: Mutex<HashMap<String, Arc<Regex>>> = Mutex::new(HashMap::new());
+}
+
+/// Compile `pattern`, or fetch it from `BYPASS_REGEX_CACHE` if a previous
+/// call already compiled it.
+fn compiled_bypass_regex(pattern: &str) -> Result<Arc<Regex>, Error> {
+    let mut cache = BYPASS_REGEX_CACHE.lock().unwrap();
+    if let Some(regex) = cache.get(pattern) {
+        return Ok(regex.clone());
+    }
+    let regex = Regex::new(pattern)
+        .map_err(|e| ErrorKind::InvalidBypassRegex(pattern.to_string(), e.to_string()))?;
+    let regex = Arc::new(regex);
+    cache.insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+/// Validate (and pre-compile) a hook's bypass condition, so that a bad
+/// regex pattern is reported as a config error when the hook is
+/// registered, rather than surfacing silently the first time a changeset
+/// tries to match against it.
+fn validate_bypass(bypass: &HookBypass) -> Result<(), Error> {
+    match bypass {
+        HookBypass::CommitMessageRegex(pattern) | HookBypass::PushvarRegex { pattern, .. } => {
+            compiled_bypass_regex(pattern)?;
+            Ok(())
+        }
+        HookBypass::CommitMessage(_) | HookBypass::Pushvar { .. } => Ok(()),
+    }
+}
+
 /// Manages hooks and allows them to be installed and uninstalled given a name
 /// Knows how to run hooks
 
@@ -158,9 +197,13 @@ impl HookManager {
         hook_name: &str,
         hook: Arc<Hook<HookChangeset>>,
         config: HookConfig,
-    ) {
+    ) -> Result<(), Error> {
+        if let Some(ref bypass) = config.bypass {
+            validate_bypass(bypass)?;
+        }
         self.changeset_hooks
             .insert(hook_name.to_string(), (hook, config));
+        Ok(())
     }
 
     pub fn register_file_hook(
@@ -168,9 +211,13 @@ impl HookManager {
         hook_name: &str,
         hook: Arc<Hook<HookFile>>,
         config: HookConfig,
-    ) {
+    ) -> Result<(), Error> {
+        if let Some(ref bypass) = config.bypass {
+            validate_bypass(bypass)?;
+        }
         let mut hooks = self.file_hooks.lock().unwrap();
         hooks.insert(hook_name.to_string(), (hook, config));
+        Ok(())
     }
 
     pub fn set_hooks_for_bookmark(&mut self, bookmark: Bookmark, hooks: Vec<String>) {
@@ -453,10 +500,22 @@ impl HookManager {
         Box::new((hg_changeset, changed_files).into_future().and_then(
             move |(changeset, changed_files)| {
                 let author = str::from_utf8(changeset.user())?.into();
+                let parent_changeset_id = match changeset.parents() {
+                    HgParents::None => None,
+                    HgParents::One(p1_hash) | HgParents::Two(p1_hash, _) => {
+                        Some(HgChangesetId::new(p1_hash))
+                    }
+                };
                 let files = changed_files
                     .into_iter()
                     .map(|(path, ty)| {
-                        HookFile::new(path, content_store.clone(), changeset_id.clone(), ty)
+                        HookFile::new(
+                            path,
+                            content_store.clone(),
+                            changeset_id.clone(),
+                            parent_changeset_id.clone(),
+                            ty,
+                        )
                     })
                     .collect();
                 let comments = str::from_utf8(changeset.comments())?.into();
@@ -505,19 +564,45 @@ impl HookManager {
     ) -> bool {
         match bypass {
             HookBypass::CommitMessage(bypass_string) => cs_msg.contains(bypass_string),
-            HookBypass::Pushvar { name, value } => {
+            HookBypass::Pushvar {
+                name,
+                value,
+                conversion,
+            } => {
                 if let Some(pushvars) = maybe_pushvars {
-                    let pushvar_val = pushvars
-                        .get(name)
-                        .map(|bytes| String::from_utf8(bytes.to_vec()));
-
-                    if let Some(Ok(pushvar_val)) = pushvar_val {
-                        return &pushvar_val == value;
+                    if let Some(raw) = pushvars.get(name) {
+                        // When a conversion is declared, `value` is a typed
+                        // comparison, e.g. ">=3" or "==1", letting the
+                        // bypass gate on more than raw string equality.
+                        if let Some(conversion) = conversion {
+                            return conversion::evaluate_condition(raw, conversion, value)
+                                .unwrap_or(false);
+                        }
+                        if let Ok(pushvar_val) = String::from_utf8(raw.to_vec()) {
+                            return &pushvar_val == value;
+                        }
                     }
                     return false;
                 }
                 return false;
             }
+            HookBypass::CommitMessageRegex(pattern) => {
+                match compiled_bypass_regex(pattern) {
+                    Ok(regex) => regex.is_match(cs_msg.as_bytes()),
+                    // Registration already validated the pattern, so this
+                    // can only fail if the cache was somehow bypassed.
+                    Err(_) => false,
+                }
+            }
+            HookBypass::PushvarRegex { name, pattern } => {
+                let regex = match compiled_bypass_regex(pattern) {
+                    Ok(regex) => regex,
+                    Err(_) => return false,
+                };
+                maybe_pushvars
+                    .and_then(|pushvars| pushvars.get(name))
+                    .map_or(false, |value| regex.is_match(value))
+            }
         }
     }
 }
@@ -584,6 +669,9 @@ pub struct HookFile {
     pub path: String,
     content_store: Arc<FileContentStore>,
     changeset_id: HgChangesetId,
+    // The changeset's first parent, if any, used to look up the file's
+    // pre-change content for diff-based hooks.
+    parent_changeset_id: Option<HgChangesetId>,
     ty: ChangedFileType,
 }
 
@@ -623,12 +711,14 @@ impl HookFile {
         path: String,
         content_store: Arc<FileContentStore>,
         changeset_id: HgChangesetId,
+        parent_changeset_id: Option<HgChangesetId>,
         ty: ChangedFileType,
     ) -> HookFile {
         HookFile {
             path,
             content_store,
             changeset_id,
+            parent_changeset_id,
             ty,
         }
     }
@@ -672,6 +762,41 @@ impl HookFile {
             .map(|(file_type, _)| file_type)
             .boxify()
     }
+
+    /// The file's type, size, and content as a lazily-pulled stream,
+    /// instead of eagerly materializing the whole file the way
+    /// `file_content` does. Lets hooks that only need the size or a sniff
+    /// of the first chunk (a size-limit or binary-content check, say)
+    /// short-circuit without paying for the rest of the file.
+    pub fn content_stream(
+        &self,
+        ctx: CoreContext,
+    ) -> BoxFuture<(FileType, u64, BoxStream<Bytes, Error>), Error> {
+        let path = try_boxfuture!(MPath::new(self.path.as_bytes()));
+        let changeset_id = self.changeset_id.clone();
+        self.content_store
+            .get_file_content_stream_for_changeset(ctx, self.changeset_id, path.clone())
+            .and_then(move |opt| {
+                opt.ok_or(ErrorKind::NoFileContent(changeset_id, path.into()).into())
+            })
+            .boxify()
+    }
+
+    /// The file's content as of the changeset's first parent, or `None` if
+    /// the changeset has no parent, or the file didn't exist there (e.g.
+    /// it was added by this changeset). Lets diff-based hooks compare old
+    /// and new content instead of only seeing the new version.
+    pub fn parent_file_content(&self, ctx: CoreContext) -> BoxFuture<Option<Bytes>, Error> {
+        let parent_changeset_id = match self.parent_changeset_id {
+            Some(id) => id,
+            None => return finished(None).boxify(),
+        };
+        let path = try_boxfuture!(MPath::new(self.path.as_bytes()));
+        self.content_store
+            .get_file_content_for_changeset(ctx, parent_changeset_id, path)
+            .map(|opt| opt.map(|(_, bytes)| bytes))
+            .boxify()
+    }
 }
 
 impl HookChangeset {
@@ -757,6 +882,12 @@ pub trait ChangesetStore: Send + Sync {
 
 pub struct InMemoryChangesetStore {
     map: HashMap<HgChangesetId, HgBlobChangeset>,
+    // The full set of paths present in each changeset's manifest, used to
+    // tell an added file from a modified or deleted one. Populated via
+    // `insert_manifest`. Without a manifest on record for the changeset
+    // itself, no touched path is reported as `Deleted`; without one for any
+    // of its parents, every remaining touched path is reported as `Added`.
+    manifests: HashMap<HgChangesetId, HashSet<String>>,
 }
 
 impl ChangesetStore for InMemoryChangesetStore {
@@ -779,13 +910,39 @@ impl ChangesetStore for InMemoryChangesetStore {
         changesetid: &HgChangesetId,
     ) -> BoxFuture<Vec<(String, ChangedFileType)>, Error> {
         match self.map.get(changesetid) {
-            Some(cs) => Box::new(finished(
-                cs.files()
-                    .into_iter()
-                    .map(|arr| String::from_utf8_lossy(&arr.to_vec()).into_owned())
-                    .map(|path| (path, ChangedFileType::Added))
-                    .collect(),
-            )),
+            Some(cs) => {
+                let parent_manifests: Vec<&HashSet<String>> = match cs.parents() {
+                    HgParents::None => vec![],
+                    HgParents::One(p1_hash) => self
+                        .manifests
+                        .get(&HgChangesetId::new(p1_hash))
+                        .into_iter()
+                        .collect(),
+                    HgParents::Two(p1_hash, p2_hash) => [p1_hash, p2_hash]
+                        .iter()
+                        .filter_map(|hash| self.manifests.get(&HgChangesetId::new(*hash)))
+                        .collect(),
+                };
+                let own_manifest = self.manifests.get(changesetid);
+
+                Box::new(finished(
+                    cs.files()
+                        .into_iter()
+                        .map(|arr| String::from_utf8_lossy(&arr.to_vec()).into_owned())
+                        .map(|path| {
+                            let still_present = own_manifest.map_or(true, |m| m.contains(&path));
+                            let ty = if !still_present {
+                                ChangedFileType::Deleted
+                            } else if parent_manifests.iter().any(|m| m.contains(&path)) {
+                                ChangedFileType::Modified
+                            } else {
+                                ChangedFileType::Added
+                            };
+                            (path, ty)
+                        })
+                        .collect(),
+                ))
+            }
             None => Box::new(failed(
                 ErrorKind::NoSuchChangeset(changesetid.to_string()).into(),
             )),
@@ -797,12 +954,20 @@ impl InMemoryChangesetStore {
     pub fn new() -> InMemoryChangesetStore {
         InMemoryChangesetStore {
             map: HashMap::new(),
+            manifests: HashMap::new(),
         }
     }
 
     pub fn insert(&mut self, changeset_id: &HgChangesetId, changeset: &HgBlobChangeset) {
         self.map.insert(changeset_id.clone(), changeset.clone());
     }
+
+    /// Record the full set of paths present in `changeset_id`'s manifest,
+    /// so that `get_changed_files` can tell an added file from a modified
+    /// one instead of reporting every touched path as `Added`.
+    pub fn insert_manifest(&mut self, changeset_id: &HgChangesetId, paths: HashSet<String>) {
+        self.manifests.insert(changeset_id.clone(), paths);
+    }
 }
 
 pub trait FileContentStore: Send + Sync {
@@ -812,6 +977,30 @@ pub trait FileContentStore: Send + Sync {
         changesetid: HgChangesetId,
         path: MPath,
     ) -> BoxFuture<Option<(FileType, Bytes)>, Error>;
+
+    /// Like `get_file_content_for_changeset`, but hands back the file's
+    /// type and size up front and the content as a lazily-pulled stream,
+    /// so a hook that only cares about size or a binary sniff of the first
+    /// chunk doesn't have to wait for (or hold in memory) the whole file.
+    ///
+    /// The default implementation just materializes the content eagerly
+    /// and wraps it in a single-item stream; content stores backed by a
+    /// chunked or streaming source should override it.
+    fn get_file_content_stream_for_changeset(
+        &self,
+        ctx: CoreContext,
+        changesetid: HgChangesetId,
+        path: MPath,
+    ) -> BoxFuture<Option<(FileType, u64, BoxStream<Bytes, Error>)>, Error> {
+        self.get_file_content_for_changeset(ctx, changesetid, path)
+            .map(|opt| {
+                opt.map(|(file_type, bytes)| {
+                    let size = bytes.len() as u64;
+                    (file_type, size, stream::once(Ok(bytes)).boxify())
+                })
+            })
+            .boxify()
+    }
 }
 
 #[derive(Clone)]
@@ -831,6 +1020,28 @@ impl FileContentStore for InMemoryFileContentStore {
             .map(|(file_type, bytes)| (file_type.clone(), bytes.clone()));
         finished(opt).boxify()
     }
+
+    fn get_file_content_stream_for_changeset(
+        &self,
+        _ctx: CoreContext,
+        changesetid: HgChangesetId,
+        path: MPath,
+    ) -> BoxFuture<Option<(FileType, u64, BoxStream<Bytes, Error>)>, Error> {
+        const CHUNK_SIZE: usize = 8 * 1024;
+        let opt = self.map.get(&(changesetid, path)).map(|(file_type, bytes)| {
+            let size = bytes.len() as u64;
+            let chunks: Vec<Bytes> = bytes
+                .chunks(CHUNK_SIZE)
+                .map(|chunk| bytes.slice_ref(chunk))
+                .collect();
+            (
+                file_type.clone(),
+                size,
+                stream::iter_ok::<_, Error>(chunks).boxify(),
+            )
+        });
+        finished(opt).boxify()
+    }
 }
 
 impl InMemoryFileContentStore {